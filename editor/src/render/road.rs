@@ -4,6 +4,12 @@ use ezgui::{Color, Drawable, GfxCtx, Prerender};
 use geom::{Polygon, Pt2D};
 use map_model::{Map, Road, RoadID};
 
+// Grades steeper than this are visually clamped to the steepest color; real streets essentially
+// never exceed it, and it keeps a single weird DEM sample from blowing out the gradient.
+const MAX_DISPLAY_GRADE: f64 = 0.15;
+// Below this, a road reads as flat rather than uphill/downhill.
+const FLAT_GRADE_THRESHOLD: f64 = 0.01;
+
 pub struct DrawRoad {
     pub id: RoadID,
     zorder: isize,
@@ -12,12 +18,20 @@ pub struct DrawRoad {
 }
 
 impl DrawRoad {
-    pub fn new(r: &Road, cs: &ColorScheme, prerender: &Prerender) -> DrawRoad {
+    // `elevation_meters` is the (start, end) elevation of the road, looked up by the caller from
+    // an external DEM (or None for maps that don't have one yet); Road itself carries no
+    // elevation today.
+    pub fn new(
+        r: &Road,
+        elevation_meters: Option<(f64, f64)>,
+        cs: &ColorScheme,
+        prerender: &Prerender,
+    ) -> DrawRoad {
         DrawRoad {
             id: r.id,
             zorder: r.get_zorder(),
             draw_center_line: prerender.upload(vec![(
-                cs.get_def("road center line", Color::YELLOW),
+                grade_color(r, elevation_meters, cs),
                 r.center_pts.make_polygons(BIG_ARROW_THICKNESS),
             )]),
         }
@@ -50,3 +64,99 @@ impl Renderable for DrawRoad {
         self.zorder
     }
 }
+
+// rise/run along the road's center line. None if there's no elevation data, or the road is so
+// short that a tiny DEM sampling error would blow up the grade.
+fn grade(r: &Road, elevation_meters: Option<(f64, f64)>) -> Option<f64> {
+    let (start, end) = elevation_meters?;
+    let run = r.center_pts.length().inner_meters();
+    if run < 1.0 {
+        return None;
+    }
+    Some((end - start) / run)
+}
+
+// Colors the center line by the road's overall grade: green-ish for uphill, the normal road
+// color for flat, and a distinct warm color for downhill. Roads without elevation data just
+// render flat.
+fn grade_color(r: &Road, elevation_meters: Option<(f64, f64)>, cs: &ColorScheme) -> Color {
+    let flat = cs.get_def("road center line", Color::YELLOW);
+    let incline = cs.get_def("road center line uphill", Color::GREEN);
+    let decline = cs.get_def("road center line downhill", Color::RED);
+
+    match grade(r, elevation_meters) {
+        Some(grade) if grade > FLAT_GRADE_THRESHOLD => blend(flat, incline, grade),
+        Some(grade) if grade < -FLAT_GRADE_THRESHOLD => blend(flat, decline, -grade),
+        _ => flat,
+    }
+}
+
+// Multiplies a biking/walking base speed by how much a road's grade helps or hurts: riders slow
+// down climbing, and only modestly speed up descending (no reason to model runaway bikes). This
+// is meant to plug into the biking/walking sim's per-edge speed calculation so FasterTrips(Bike)
+// actually responds to elevation -- but that sim code, and the elevation field this needs on
+// Road itself, live outside this checkout, so nothing calls it yet.
+pub fn bike_speed_multiplier(grade: f64) -> f64 {
+    if grade >= 0.0 {
+        (1.0 - 4.0 * grade).max(0.25)
+    } else {
+        (1.0 - 1.5 * grade).min(1.5)
+    }
+}
+
+// Linearly interpolates from `flat` towards `extreme` as `grade` approaches MAX_DISPLAY_GRADE.
+fn blend(flat: Color, extreme: Color, grade: f64) -> Color {
+    let pct = (grade.abs() / MAX_DISPLAY_GRADE).min(1.0);
+    Color::rgba_f(
+        flat.r + (extreme.r - flat.r) * pct as f32,
+        flat.g + (extreme.g - flat.g) * pct as f32,
+        flat.b + (extreme.b - flat.b) * pct as f32,
+        1.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_is_identity_at_zero_grade() {
+        let flat = Color::rgba_f(1.0, 1.0, 0.0, 1.0);
+        let incline = Color::rgba_f(0.0, 1.0, 0.0, 1.0);
+        assert_eq!(blend(flat, incline, 0.0), flat);
+    }
+
+    #[test]
+    fn blend_reaches_the_extreme_past_the_display_cap() {
+        let flat = Color::rgba_f(1.0, 1.0, 0.0, 1.0);
+        let incline = Color::rgba_f(0.0, 1.0, 0.0, 1.0);
+        assert_eq!(blend(flat, incline, MAX_DISPLAY_GRADE * 10.0), incline);
+    }
+
+    #[test]
+    fn blend_clamps_beyond_the_display_cap_the_same_as_at_the_cap() {
+        let flat = Color::rgba_f(1.0, 1.0, 0.0, 1.0);
+        let incline = Color::rgba_f(0.0, 1.0, 0.0, 1.0);
+        assert_eq!(
+            blend(flat, incline, MAX_DISPLAY_GRADE),
+            blend(flat, incline, MAX_DISPLAY_GRADE * 5.0)
+        );
+    }
+
+    #[test]
+    fn bike_speed_multiplier_is_identity_on_flat_ground() {
+        assert_eq!(bike_speed_multiplier(0.0), 1.0);
+    }
+
+    #[test]
+    fn bike_speed_multiplier_slows_down_climbing_and_floors_out() {
+        assert!(bike_speed_multiplier(0.05) < 1.0);
+        assert_eq!(bike_speed_multiplier(10.0), 0.25);
+    }
+
+    #[test]
+    fn bike_speed_multiplier_speeds_up_descending_and_caps_out() {
+        assert!(bike_speed_multiplier(-0.05) > 1.0);
+        assert_eq!(bike_speed_multiplier(-10.0), 1.5);
+    }
+}
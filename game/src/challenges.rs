@@ -3,7 +3,7 @@ use crate::sandbox::{GameplayMode, SandboxMode};
 use crate::ui::UI;
 use abstutil::Timer;
 use ezgui::{
-    hotkey, Choice, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, ModalMenu, Text,
+    hotkey, Choice, Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, ModalMenu, Text,
     VerticalAlignment,
 };
 use geom::{Duration, DurationHistogram, DurationStats};
@@ -58,6 +58,20 @@ fn all_challenges() -> Vec<Challenge> {
             map_name: "montlake".to_string(),
             gameplay: GameplayMode::FasterTrips(TripMode::Drive),
         },
+        Challenge {
+            title: "Don't spread the disease".to_string(),
+            description: "Minimize the number of people ever infected, by reducing crowding \
+                           on transit and in buildings"
+                .to_string(),
+            map_name: "montlake".to_string(),
+            gameplay: GameplayMode::MinimizeInfections,
+        },
+        Challenge {
+            title: "Reduce fleet emissions".to_string(),
+            description: "Minimize total energy used and CO2 emitted by all vehicles".to_string(),
+            map_name: "montlake".to_string(),
+            gameplay: GameplayMode::ReduceEmissions,
+        },
     ]
 }
 
@@ -132,19 +146,22 @@ impl State for ChallengeSplash {
 
 pub fn prebake() {
     let mut timer = Timer::new("prebake all challenge results");
-    let mut results = PrebakedResults {
-        faster_trips: BTreeMap::new(),
-    };
-    prebake_faster_trips(&mut results, "montlake", &mut timer);
+    let mut results = PrebakedResults::new();
+    for challenge in all_challenges() {
+        prebake_challenge(&challenge, &mut results, &mut timer);
+    }
     abstutil::write_json("../data/prebaked_results.json", &results).unwrap();
 }
 
-fn prebake_faster_trips(results: &mut PrebakedResults, map_name: &str, timer: &mut Timer) {
-    timer.start(&format!("prebake faster trips on {}", map_name));
+fn prebake_challenge(challenge: &Challenge, results: &mut PrebakedResults, timer: &mut Timer) {
+    timer.start(&format!(
+        "prebake \"{}\" on {}",
+        challenge.title, challenge.map_name
+    ));
 
     let (map, mut sim, _) = SimFlags {
         load: abstutil::path1_bin(
-            map_name,
+            &challenge.map_name,
             abstutil::SCENARIOS,
             "weekday_typical_traffic_from_psrc",
         ),
@@ -156,6 +173,41 @@ fn prebake_faster_trips(results: &mut PrebakedResults, map_name: &str, timer: &m
     sim.timed_step(&map, Duration::END_OF_DAY, timer);
 
     timer.start("collect results");
+    let per_mode = compute_per_mode_stats(&sim);
+
+    let mut bus_wait_times = BTreeMap::new();
+    if let GameplayMode::OptimizeBus(ref route) = challenge.gameplay {
+        bus_wait_times.insert(route.clone(), sim.get_bus_wait_time_stats(route));
+    }
+    let total_ever_infected = if challenge.gameplay == GameplayMode::MinimizeInfections {
+        sim.get_pandemic_model().map(|p| p.total_ever_infected())
+    } else {
+        None
+    };
+    let total_fleet_co2_grams = if challenge.gameplay == GameplayMode::ReduceEmissions {
+        sim.get_energy_model().map(|e| e.total_fleet_co2_grams())
+    } else {
+        None
+    };
+
+    results.baselines.insert(
+        (challenge.map_name.clone(), challenge.gameplay.clone()),
+        BaselineStats {
+            per_mode,
+            bus_wait_times,
+            total_ever_infected,
+            total_fleet_co2_grams,
+        },
+    );
+    timer.stop("collect results");
+
+    timer.stop(&format!(
+        "prebake \"{}\" on {}",
+        challenge.title, challenge.map_name
+    ));
+}
+
+fn compute_per_mode_stats(sim: &sim::Sim) -> BTreeMap<TripMode, DurationStats> {
     let mut distribs: BTreeMap<TripMode, DurationHistogram> = BTreeMap::new();
     for m in vec![
         TripMode::Walk,
@@ -168,18 +220,181 @@ fn prebake_faster_trips(results: &mut PrebakedResults, map_name: &str, timer: &m
     for (_, m, dt) in sim.get_finished_trips().finished_trips {
         distribs.get_mut(&m).unwrap().add(dt);
     }
-    for (m, distrib) in distribs {
-        results.faster_trips.insert(m, distrib.to_stats());
-    }
-    timer.stop("collect results");
-
-    timer.stop(&format!("prebake faster trips on {}", map_name));
+    distribs
+        .into_iter()
+        .map(|(m, distrib)| (m, distrib.to_stats()))
+        .collect()
 }
 
-// TODO Something more general?
-// - key by GameplayMode (which needs map name too maybe)
-// - different baselines/benchmarks
 #[derive(Serialize, Deserialize)]
 pub struct PrebakedResults {
-    pub faster_trips: BTreeMap<TripMode, DurationStats>,
+    pub baselines: BTreeMap<(String, GameplayMode), BaselineStats>,
+}
+
+impl PrebakedResults {
+    fn new() -> PrebakedResults {
+        PrebakedResults {
+            baselines: BTreeMap::new(),
+        }
+    }
+
+    fn get(&self, map_name: &str, gameplay: &GameplayMode) -> Option<&BaselineStats> {
+        self.baselines
+            .get(&(map_name.to_string(), gameplay.clone()))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BaselineStats {
+    pub per_mode: BTreeMap<TripMode, DurationStats>,
+    // Keyed by bus route name, only populated for OptimizeBus challenges.
+    pub bus_wait_times: BTreeMap<String, DurationStats>,
+    // Only populated for MinimizeInfections challenges.
+    pub total_ever_infected: Option<usize>,
+    // Only populated for ReduceEmissions challenges.
+    pub total_fleet_co2_grams: Option<f64>,
+}
+
+// Continuously compares the player's running sim against the baked baseline for the active
+// challenge and shows a signed delta per metric, like a racing delta display. Which metrics show
+// up depends on the GameplayMode -- an OptimizeBus run also gets a bus wait time line, etc.
+pub struct DeltaBoard {
+    map_name: String,
+    gameplay: GameplayMode,
+    baseline: Option<BaselineStats>,
+    // The best (smallest) delta observed so far this session, keyed by the metric's label.
+    best_ever: BTreeMap<String, f64>,
+}
+
+enum DeltaUnit {
+    Duration,
+    Count,
+}
+
+impl DeltaUnit {
+    fn fmt(&self, v: f64) -> String {
+        match self {
+            DeltaUnit::Duration => {
+                let d = Duration::seconds(v.abs());
+                if v < 0.0 {
+                    format!("-{}", d)
+                } else {
+                    format!("+{}", d)
+                }
+            }
+            DeltaUnit::Count => {
+                if v < 0.0 {
+                    format!("{:.0}", v)
+                } else {
+                    format!("+{:.0}", v)
+                }
+            }
+        }
+    }
+}
+
+impl DeltaBoard {
+    pub fn new(map_name: &str, gameplay: GameplayMode) -> DeltaBoard {
+        let baseline = abstutil::maybe_read_json::<PrebakedResults>(
+            "../data/prebaked_results.json",
+            &mut Timer::throwaway(),
+        )
+        .ok()
+        .and_then(|r| r.get(map_name, &gameplay).cloned());
+        DeltaBoard {
+            map_name: map_name.to_string(),
+            gameplay,
+            baseline,
+            best_ever: BTreeMap::new(),
+        }
+    }
+
+    // Call whenever a trip finishes in the running sim; recomputes every delta relevant to the
+    // active challenge against the baked baseline.
+    pub fn panel(&mut self, sim: &sim::Sim) -> Text {
+        let mut txt = Text::new();
+        let baseline = match self.baseline {
+            Some(ref b) => b,
+            None => {
+                txt.add(Line(format!(
+                    "No baseline baked for {} / {:?}",
+                    self.map_name, self.gameplay
+                )));
+                return txt;
+            }
+        };
+
+        for (mode, live_stats) in compute_per_mode_stats(sim) {
+            if let Some(base_stats) = baseline.per_mode.get(&mode) {
+                let delta = (live_stats.p50 - base_stats.p50).inner_seconds();
+                self.add_delta_line(
+                    &mut txt,
+                    format!("{:?} 50%ile", mode),
+                    delta,
+                    DeltaUnit::Duration,
+                );
+            }
+        }
+
+        if let GameplayMode::OptimizeBus(ref route) = self.gameplay {
+            if let Some(base_stats) = baseline.bus_wait_times.get(route) {
+                let live_stats = sim.get_bus_wait_time_stats(route);
+                let delta = (live_stats.p50 - base_stats.p50).inner_seconds();
+                self.add_delta_line(
+                    &mut txt,
+                    format!("Route {} wait 50%ile", route),
+                    delta,
+                    DeltaUnit::Duration,
+                );
+            }
+        }
+
+        if self.gameplay == GameplayMode::MinimizeInfections {
+            if let (Some(base), Some(model)) =
+                (baseline.total_ever_infected, sim.get_pandemic_model())
+            {
+                let delta = model.total_ever_infected() as f64 - base as f64;
+                self.add_delta_line(
+                    &mut txt,
+                    "Total ever infected".to_string(),
+                    delta,
+                    DeltaUnit::Count,
+                );
+            }
+        }
+
+        if self.gameplay == GameplayMode::ReduceEmissions {
+            if let (Some(base), Some(model)) =
+                (baseline.total_fleet_co2_grams, sim.get_energy_model())
+            {
+                let delta = model.total_fleet_co2_grams() - base;
+                self.add_delta_line(&mut txt, "Fleet CO2 (g)".to_string(), delta, DeltaUnit::Count);
+            }
+        }
+
+        txt
+    }
+
+    fn add_delta_line(&mut self, txt: &mut Text, label: String, delta: f64, unit: DeltaUnit) {
+        let best = self
+            .best_ever
+            .get(&label)
+            .cloned()
+            .map(|b| delta.min(b))
+            .unwrap_or(delta);
+        self.best_ever.insert(label.clone(), best);
+
+        let color = if delta == 0.0 {
+            Color::WHITE
+        } else if delta < 0.0 {
+            Color::GREEN
+        } else {
+            Color::RED
+        };
+        txt.add_appended(vec![
+            Line(format!("{}: ", label)),
+            Line(unit.fmt(delta)).fg(color),
+            Line(format!("  (best: {})", unit.fmt(best))),
+        ]);
+    }
 }
@@ -0,0 +1,58 @@
+// Copyright 2018 Google LLC, licensed under http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::challenges::DeltaBoard;
+use crate::game::{State, Transition};
+use crate::ui::UI;
+use ezgui::{EventCtx, GfxCtx, HorizontalAlignment, Text, VerticalAlignment};
+use serde_derive::{Deserialize, Serialize};
+use sim::TripMode;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum GameplayMode {
+    OptimizeBus(String),
+    CreateGridlock,
+    FasterTrips(TripMode),
+    MinimizeInfections,
+    ReduceEmissions,
+}
+
+pub struct SandboxMode {
+    gameplay: GameplayMode,
+    delta_board: DeltaBoard,
+    delta_board_text: Text,
+    // How many trips had finished the last time we rebuilt delta_board_text. The panel only
+    // actually changes once a trip finishes, so there's no point rebuilding it on every event.
+    last_finished_trips: usize,
+}
+
+impl SandboxMode {
+    pub fn new(_ctx: &mut EventCtx, ui: &mut UI, gameplay: GameplayMode) -> SandboxMode {
+        let mut delta_board = DeltaBoard::new(ui.primary.map.get_name(), gameplay.clone());
+        let delta_board_text = delta_board.panel(&ui.primary.sim);
+        let last_finished_trips = ui.primary.sim.get_finished_trips().finished_trips.len();
+        SandboxMode {
+            gameplay,
+            delta_board,
+            delta_board_text,
+            last_finished_trips,
+        }
+    }
+}
+
+impl State for SandboxMode {
+    fn event(&mut self, _ctx: &mut EventCtx, ui: &mut UI) -> Transition {
+        let finished_trips = ui.primary.sim.get_finished_trips().finished_trips.len();
+        if finished_trips != self.last_finished_trips {
+            self.last_finished_trips = finished_trips;
+            self.delta_board_text = self.delta_board.panel(&ui.primary.sim);
+        }
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &UI) {
+        g.draw_blocking_text(
+            &self.delta_board_text,
+            (HorizontalAlignment::Right, VerticalAlignment::Top),
+        );
+    }
+}
@@ -2,11 +2,19 @@
 
 extern crate abstutil;
 extern crate control;
+extern crate geom;
 extern crate map_model;
 extern crate sim;
 #[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate structopt;
 
+use geom::DurationStats;
+use sim::{Scenario, Tick, TripMode};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -24,13 +32,23 @@ struct Flags {
     #[structopt(long = "save_at")]
     save_at: Option<String>,
 
-    /// Big or large random scenario?
-    #[structopt(long = "big_sim")]
-    big_sim: bool,
+    /// Name of a scenario to instantiate, if the sim is starting fresh
+    #[structopt(long = "scenario")]
+    scenario: Option<String>,
 
     /// Scenario name for savestating
     #[structopt(long = "scenario_name", default_value = "headless")]
     scenario_name: String,
+
+    /// After the sim finishes, write a structured analytics summary here
+    #[structopt(long = "analytics_out")]
+    analytics_out: Option<String>,
+
+    /// In addition to the final summary, write an incremental analytics snapshot at this
+    /// interval, to the analytics_out path suffixed with the sim time in seconds. Needs
+    /// --analytics_out.
+    #[structopt(long = "snapshot_every")]
+    snapshot_every: Option<String>,
 }
 
 fn main() {
@@ -40,20 +58,30 @@ fn main() {
         flags.load,
         flags.scenario_name,
         flags.rng_seed,
-        Some(sim::Tick::from_seconds(30)),
+        Some(Tick::from_seconds(30)),
     );
 
-    if sim.time == sim::Tick::zero() {
-        // TODO need a notion of scenarios
-        if flags.big_sim {
-            sim::init::big_spawn(&mut sim, &map);
+    if sim.time == Tick::zero() {
+        if let Some(ref scenario_name) = flags.scenario {
+            let mut timer = abstutil::Timer::new(&format!("load scenario {}", scenario_name));
+            let scenario: Scenario = abstutil::read_binary(
+                &abstutil::path1_bin(map.get_name(), abstutil::SCENARIOS, scenario_name),
+                &mut timer,
+            )
+            .unwrap();
+            scenario.instantiate(
+                &mut sim,
+                &map,
+                &mut sim::init::make_rng(flags.rng_seed),
+                &mut timer,
+            );
         } else {
             sim::init::small_spawn(&mut sim, &map);
         }
     }
 
     let save_at = if let Some(ref time_str) = flags.save_at {
-        if let Some(t) = sim::Tick::parse(time_str) {
+        if let Some(t) = Tick::parse(time_str) {
             Some(t)
         } else {
             panic!("Couldn't parse time {}", time_str);
@@ -61,18 +89,135 @@ fn main() {
     } else {
         None
     };
+    let snapshot_every = flags.snapshot_every.as_ref().map(|time_str| {
+        Tick::parse(time_str).unwrap_or_else(|| panic!("Couldn't parse time {}", time_str))
+    });
+    let analytics_out = flags.analytics_out.clone();
+    let tracker = Rc::new(RefCell::new(ThruputTracker::new()));
 
-    sim::init::run_until_done(
-        &mut sim,
-        &map,
-        &control_map,
-        vec![Box::new(move |sim| {
-            if Some(sim.time) == save_at {
-                sim.save();
-                true
-            } else {
-                false
+    let mut callbacks: Vec<Box<dyn FnMut(&mut sim::Sim) -> bool>> = Vec::new();
+    callbacks.push(Box::new(move |sim| {
+        if Some(sim.time) == save_at {
+            sim.save();
+            true
+        } else {
+            false
+        }
+    }));
+    if let (Some(every), Some(path)) = (snapshot_every, analytics_out.clone()) {
+        let mut next_snapshot = every;
+        let tracker = tracker.clone();
+        callbacks.push(Box::new(move |sim| {
+            if sim.time >= next_snapshot {
+                let snapshot_path = format!("{}.{}", path, sim.time.as_seconds());
+                write_analytics(sim, &snapshot_path, &mut tracker.borrow_mut());
+                next_snapshot = Tick::from_seconds(next_snapshot.as_seconds() + every.as_seconds());
             }
-        })],
-    );
+            false
+        }));
+    }
+
+    sim::init::run_until_done(&mut sim, &map, &control_map, callbacks);
+
+    if let Some(ref path) = flags.analytics_out {
+        write_analytics(&sim, path, &mut tracker.borrow_mut());
+    }
+}
+
+// Reuses the sim's own bookkeeping (DurationHistogram/DurationStats, per-road and
+// per-intersection throughput counters) instead of recomputing stats here.
+#[derive(Serialize)]
+struct AnalyticsSnapshot {
+    time: Tick,
+    finished_trips_by_mode: BTreeMap<TripMode, DurationStats>,
+    // Throughput since the previous snapshot (or since the start of the sim, for the first one),
+    // not the sim's cumulative running total.
+    thruput_roads: BTreeMap<map_model::RoadID, usize>,
+    thruput_intersections: BTreeMap<map_model::IntersectionID, usize>,
+    unfinished_trips: usize,
+    // Only non-empty when the scenario seeded a PandemicModel.
+    seir_counts: BTreeMap<sim::pandemic::SEIRState, usize>,
+    // Only set when the map has fuel/charging stations and vehicles carry an EnergyModel.
+    total_fleet_energy: Option<f64>,
+    total_fleet_co2_grams: Option<f64>,
+    unreachable_trips: usize,
+}
+
+// Tracks the sim's cumulative throughput counters between calls so each snapshot can report a
+// per-window delta instead of the ever-growing total.
+struct ThruputTracker {
+    prev_roads: BTreeMap<map_model::RoadID, usize>,
+    prev_intersections: BTreeMap<map_model::IntersectionID, usize>,
+}
+
+impl ThruputTracker {
+    fn new() -> ThruputTracker {
+        ThruputTracker {
+            prev_roads: BTreeMap::new(),
+            prev_intersections: BTreeMap::new(),
+        }
+    }
+
+    fn window(
+        &mut self,
+        cumulative_roads: &BTreeMap<map_model::RoadID, usize>,
+        cumulative_intersections: &BTreeMap<map_model::IntersectionID, usize>,
+    ) -> (
+        BTreeMap<map_model::RoadID, usize>,
+        BTreeMap<map_model::IntersectionID, usize>,
+    ) {
+        let roads = delta_counts(&self.prev_roads, cumulative_roads);
+        let intersections = delta_counts(&self.prev_intersections, cumulative_intersections);
+        self.prev_roads = cumulative_roads.clone();
+        self.prev_intersections = cumulative_intersections.clone();
+        (roads, intersections)
+    }
+}
+
+fn delta_counts<K: Ord + Clone>(
+    prev: &BTreeMap<K, usize>,
+    cur: &BTreeMap<K, usize>,
+) -> BTreeMap<K, usize> {
+    cur.iter()
+        .map(|(k, v)| (k.clone(), v.saturating_sub(*prev.get(k).unwrap_or(&0))))
+        .collect()
+}
+
+fn write_analytics(sim: &sim::Sim, path: &str, tracker: &mut ThruputTracker) {
+    let a = sim.get_analytics();
+
+    let mut finished_trips_by_mode = BTreeMap::new();
+    for mode in vec![
+        TripMode::Walk,
+        TripMode::Bike,
+        TripMode::Transit,
+        TripMode::Drive,
+    ] {
+        finished_trips_by_mode.insert(mode, a.finished_trip_distribution(mode).to_stats());
+    }
+
+    let (thruput_roads, thruput_intersections) =
+        tracker.window(&a.thruput_stats.count_per_road, &a.thruput_stats.count_per_intersection);
+
+    let energy = sim.get_energy_model();
+    let snapshot = AnalyticsSnapshot {
+        time: sim.time,
+        finished_trips_by_mode,
+        thruput_roads,
+        thruput_intersections,
+        // Counts trips that haven't started yet too, not just ones already underway --
+        // get_all_trip_phases() only covers trips that have actually begun.
+        unfinished_trips: sim
+            .get_scheduled_trip_count()
+            .saturating_sub(a.finished_trips.len()),
+        seir_counts: sim
+            .get_pandemic_model()
+            .map(|p| p.state_counts())
+            .unwrap_or_else(BTreeMap::new),
+        total_fleet_energy: energy.map(|e| e.total_fleet_energy()),
+        total_fleet_co2_grams: energy.map(|e| e.total_fleet_co2_grams()),
+        unreachable_trips: energy.map(|e| e.unreachable_trips().len()).unwrap_or(0),
+    };
+    abstutil::write_json(path, &snapshot).expect("Writing analytics output failed");
+    println!("Wrote analytics snapshot for {} to {}", sim.time, path);
 }
@@ -0,0 +1,235 @@
+// Copyright 2018 Google LLC, licensed under http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::{CarID, Tick};
+use geom::Distance;
+use map_model::BuildingID;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Powertrain {
+    Combustion,
+    Electric,
+}
+
+impl Powertrain {
+    // Energy used per meter driven, ignoring grade. Arbitrary but internally consistent units
+    // (liters for combustion, kWh for electric), good enough for relative scoring.
+    fn base_consumption_per_meter(self) -> f64 {
+        match self {
+            Powertrain::Combustion => 0.00009,
+            Powertrain::Electric => 0.00020,
+        }
+    }
+
+    // CO2 grams emitted per unit of energy consumed. Electric vehicles are charged off some
+    // average grid mix, so they're not zero -- just much lower than tailpipe combustion.
+    fn co2_grams_per_unit(self) -> f64 {
+        match self {
+            Powertrain::Combustion => 2300.0,
+            Powertrain::Electric => 120.0,
+        }
+    }
+}
+
+pub struct VehicleEnergy {
+    pub powertrain: Powertrain,
+    pub capacity: f64,
+    charge: f64,
+}
+
+impl VehicleEnergy {
+    pub fn new(powertrain: Powertrain, capacity: f64) -> VehicleEnergy {
+        VehicleEnergy {
+            powertrain,
+            capacity,
+            charge: capacity,
+        }
+    }
+
+    pub fn remaining_range_meters(&self) -> f64 {
+        self.charge / self.powertrain.base_consumption_per_meter()
+    }
+
+    // `grade` is rise/run; uphill driving costs more energy, downhill regenerative braking (for
+    // electrics) or engine braking (for combustion) gives a little back.
+    fn consume(&mut self, dist: Distance, grade: f64) -> f64 {
+        let grade_multiplier = (1.0 + 3.0 * grade).max(0.2);
+        let used = dist.inner_meters() * self.powertrain.base_consumption_per_meter() * grade_multiplier;
+        self.charge = (self.charge - used).max(0.0);
+        used
+    }
+
+    fn refuel(&mut self) {
+        self.charge = self.capacity;
+    }
+}
+
+// A refueling/charging POI. Combustion vehicles only stop at Fuel stations, electrics only at
+// Charger stations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum StationType {
+    Fuel,
+    Charger,
+}
+
+pub struct EnergyModel {
+    vehicles: BTreeMap<CarID, VehicleEnergy>,
+    stations: BTreeMap<BuildingID, StationType>,
+    // Detour inserted once remaining range drops below the trip's remaining distance plus this
+    // fraction of it as a safety margin.
+    reserve_fraction: f64,
+    dwell_time: Tick,
+
+    cumulative_energy: BTreeMap<CarID, f64>,
+    cumulative_co2_grams: BTreeMap<CarID, f64>,
+    // Vehicles that started a trip longer than their remaining range when the map has no station
+    // of their type at all -- flagged instead of deadlocking the sim mid-route. Whether an
+    // existing station lies within range is not checked.
+    unreachable: Vec<CarID>,
+}
+
+impl EnergyModel {
+    pub fn new(stations: BTreeMap<BuildingID, StationType>) -> EnergyModel {
+        EnergyModel {
+            vehicles: BTreeMap::new(),
+            stations,
+            reserve_fraction: 0.15,
+            dwell_time: Tick::from_seconds(5 * 60),
+            cumulative_energy: BTreeMap::new(),
+            cumulative_co2_grams: BTreeMap::new(),
+            unreachable: Vec::new(),
+        }
+    }
+
+    pub fn register_vehicle(&mut self, car: CarID, powertrain: Powertrain, capacity: f64) {
+        self.vehicles
+            .insert(car, VehicleEnergy::new(powertrain, capacity));
+    }
+
+    // Call when a vehicle starts a trip with its full planned distance, so we can flag trips
+    // that are doomed from the outset rather than letting them strand mid-route.
+    pub fn check_trip_feasible(&mut self, car: CarID, planned_dist_meters: f64) -> bool {
+        let (remaining, powertrain) = match self.vehicles.get(&car) {
+            Some(v) => (v.remaining_range_meters(), v.powertrain),
+            None => return true,
+        };
+        if remaining < planned_dist_meters && self.nearest_station(powertrain).is_none() {
+            self.unreachable.push(car);
+            return false;
+        }
+        true
+    }
+
+    pub fn step_along_path(&mut self, car: CarID, dist: Distance, grade: f64) {
+        let used = match self.vehicles.get_mut(&car) {
+            Some(v) => v.consume(dist, grade),
+            None => return,
+        };
+        let powertrain = self.vehicles[&car].powertrain;
+        *self.cumulative_energy.entry(car).or_insert(0.0) += used;
+        *self.cumulative_co2_grams.entry(car).or_insert(0.0) +=
+            used * powertrain.co2_grams_per_unit();
+    }
+
+    // Should the router detour to the nearest matching station before continuing?
+    pub fn needs_refuel(&self, car: CarID, remaining_trip_dist_meters: f64) -> bool {
+        match self.vehicles.get(&car) {
+            Some(v) => {
+                v.remaining_range_meters() < remaining_trip_dist_meters * (1.0 + self.reserve_fraction)
+            }
+            None => false,
+        }
+    }
+
+    pub fn nearest_station(&self, powertrain: Powertrain) -> Option<BuildingID> {
+        let want = match powertrain {
+            Powertrain::Combustion => StationType::Fuel,
+            Powertrain::Electric => StationType::Charger,
+        };
+        // TODO Actually pick the nearest one by map distance, not just the first match.
+        self.stations
+            .iter()
+            .find(|(_, t)| **t == want)
+            .map(|(b, _)| *b)
+    }
+
+    pub fn dwell_time(&self) -> Tick {
+        self.dwell_time
+    }
+
+    pub fn refuel(&mut self, car: CarID) {
+        if let Some(v) = self.vehicles.get_mut(&car) {
+            v.refuel();
+        }
+    }
+
+    pub fn total_fleet_energy(&self) -> f64 {
+        self.cumulative_energy.values().sum()
+    }
+
+    pub fn total_fleet_co2_grams(&self) -> f64 {
+        self.cumulative_co2_grams.values().sum()
+    }
+
+    pub fn unreachable_trips(&self) -> &Vec<CarID> {
+        &self.unreachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consume_drains_charge_proportional_to_distance_and_grade() {
+        let mut v = VehicleEnergy::new(Powertrain::Combustion, 1.0);
+        let used = v.consume(Distance::meters(1000.0), 0.0);
+        assert_eq!(used, 1000.0 * Powertrain::Combustion.base_consumption_per_meter());
+        assert_eq!(v.charge, 1.0 - used);
+    }
+
+    #[test]
+    fn consume_uphill_costs_more_than_flat() {
+        let mut flat = VehicleEnergy::new(Powertrain::Combustion, 1.0);
+        let mut uphill = VehicleEnergy::new(Powertrain::Combustion, 1.0);
+        let flat_used = flat.consume(Distance::meters(1000.0), 0.0);
+        let uphill_used = uphill.consume(Distance::meters(1000.0), 0.1);
+        assert!(uphill_used > flat_used);
+    }
+
+    #[test]
+    fn consume_never_drains_charge_below_zero() {
+        let mut v = VehicleEnergy::new(Powertrain::Combustion, 0.001);
+        v.consume(Distance::meters(1_000_000.0), 0.0);
+        assert_eq!(v.charge, 0.0);
+    }
+
+    #[test]
+    fn needs_refuel_once_remaining_range_is_within_the_reserve_fraction() {
+        let mut model = EnergyModel::new(BTreeMap::new());
+        let car = CarID(0);
+        // A nearly-empty tank can't possibly cover a long remaining trip.
+        model.register_vehicle(car, Powertrain::Combustion, 0.0001);
+        assert!(model.needs_refuel(car, 10_000.0));
+
+        // About 11km of range covers a 10km trip, but not with the 15% reserve on top.
+        let reserve_car = CarID(1);
+        model.register_vehicle(reserve_car, Powertrain::Combustion, 0.99);
+        assert!(model.needs_refuel(reserve_car, 10_000.0));
+    }
+
+    #[test]
+    fn needs_refuel_is_false_with_plenty_of_range_left() {
+        let mut model = EnergyModel::new(BTreeMap::new());
+        let car = CarID(0);
+        model.register_vehicle(car, Powertrain::Combustion, 1000.0);
+        assert!(!model.needs_refuel(car, 1.0));
+    }
+
+    #[test]
+    fn needs_refuel_is_false_for_an_unregistered_vehicle() {
+        let model = EnergyModel::new(BTreeMap::new());
+        assert!(!model.needs_refuel(CarID(0), 10_000.0));
+    }
+}
@@ -0,0 +1,7 @@
+// Copyright 2018 Google LLC, licensed under http://www.apache.org/licenses/LICENSE-2.0
+
+pub mod energy;
+pub mod pandemic;
+mod sim_state;
+
+pub use crate::sim_state::Sim;
@@ -0,0 +1,353 @@
+// Copyright 2018 Google LLC, licensed under http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::{CarID, PersonID, Tick};
+use map_model::BuildingID;
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum SEIRState {
+    Susceptible,
+    Exposed,
+    Infected,
+    Recovered,
+}
+
+// A building or transit vehicle that people currently occupy. Transmission is computed when
+// somebody leaves -- at that point we know the full overlap with every infected person who was
+// ever present during their stay, not just whoever happens to still be in the room.
+#[derive(Default)]
+struct EnclosedSpace {
+    // When did each currently-present person arrive?
+    present: BTreeMap<PersonID, Tick>,
+    // [arrival, departure) windows of every infected person who has already left this space.
+    // Kept so a susceptible person who overlapped with them earlier in their own stay still gets
+    // credited for that exposure after the infected person departs.
+    past_infected_intervals: Vec<(Tick, Tick)>,
+}
+
+impl EnclosedSpace {
+    // [arrival, departure) windows, during `now`, of every infected person who has occupied this
+    // space at any point -- past departures plus anyone still currently present (whose window
+    // runs up to `now`).
+    fn infected_intervals(&self, model: &PandemicModel, now: Tick) -> Vec<(Tick, Tick)> {
+        let mut intervals = self.past_infected_intervals.clone();
+        for (p, arrived) in &self.present {
+            if model.states.get(p) == Some(&SEIRState::Infected) {
+                intervals.push((*arrived, now));
+            }
+        }
+        intervals
+    }
+}
+
+pub struct PandemicModel {
+    states: BTreeMap<PersonID, SEIRState>,
+    // When a person became Exposed or Infected, so we know when to advance them.
+    exposed_at: BTreeMap<PersonID, Tick>,
+    infected_at: BTreeMap<PersonID, Tick>,
+    incubation_period: Tick,
+    infectious_period: Tick,
+    // Expected number of transmissions per second of 1-on-1 exposure.
+    beta: f64,
+
+    buildings: BTreeMap<BuildingID, EnclosedSpace>,
+    vehicles: BTreeMap<CarID, EnclosedSpace>,
+}
+
+impl PandemicModel {
+    pub fn new(
+        rng: &mut XorShiftRng,
+        all_people: &Vec<PersonID>,
+        seed_infections: usize,
+        beta: f64,
+        incubation_period: Tick,
+        infectious_period: Tick,
+    ) -> PandemicModel {
+        let mut states = BTreeMap::new();
+        for p in all_people {
+            states.insert(*p, SEIRState::Susceptible);
+        }
+        let mut infected_at = BTreeMap::new();
+        for p in rand::seq::index::sample(rng, all_people.len(), seed_infections.min(all_people.len()))
+            .iter()
+        {
+            let person = all_people[p];
+            states.insert(person, SEIRState::Infected);
+            infected_at.insert(person, Tick::zero());
+        }
+
+        PandemicModel {
+            states,
+            exposed_at: BTreeMap::new(),
+            infected_at,
+            incubation_period,
+            infectious_period,
+            beta,
+            buildings: BTreeMap::new(),
+            vehicles: BTreeMap::new(),
+        }
+    }
+
+    pub fn person_enters_building(&mut self, now: Tick, person: PersonID, bldg: BuildingID) {
+        self.buildings
+            .entry(bldg)
+            .or_insert_with(EnclosedSpace::default)
+            .present
+            .insert(person, now);
+    }
+
+    pub fn person_leaves_building(
+        &mut self,
+        now: Tick,
+        rng: &mut XorShiftRng,
+        person: PersonID,
+        bldg: BuildingID,
+    ) {
+        let arrived = match self
+            .buildings
+            .get_mut(&bldg)
+            .and_then(|space| space.present.remove(&person))
+        {
+            Some(t) => t,
+            None => return,
+        };
+        self.maybe_transmit(now, rng, person, bldg_infected_intervals(self, bldg, now), arrived);
+        if self.states.get(&person) == Some(&SEIRState::Infected) {
+            if let Some(space) = self.buildings.get_mut(&bldg) {
+                space.past_infected_intervals.push((arrived, now));
+            }
+        }
+    }
+
+    pub fn person_boards_vehicle(&mut self, now: Tick, person: PersonID, vehicle: CarID) {
+        self.vehicles
+            .entry(vehicle)
+            .or_insert_with(EnclosedSpace::default)
+            .present
+            .insert(person, now);
+    }
+
+    pub fn person_alights_vehicle(
+        &mut self,
+        now: Tick,
+        rng: &mut XorShiftRng,
+        person: PersonID,
+        vehicle: CarID,
+    ) {
+        let arrived = match self
+            .vehicles
+            .get_mut(&vehicle)
+            .and_then(|space| space.present.remove(&person))
+        {
+            Some(t) => t,
+            None => return,
+        };
+        self.maybe_transmit(
+            now,
+            rng,
+            person,
+            vehicle_infected_intervals(self, vehicle, now),
+            arrived,
+        );
+        if self.states.get(&person) == Some(&SEIRState::Infected) {
+            if let Some(space) = self.vehicles.get_mut(&vehicle) {
+                space.past_infected_intervals.push((arrived, now));
+            }
+        }
+    }
+
+    fn maybe_transmit(
+        &mut self,
+        now: Tick,
+        rng: &mut XorShiftRng,
+        person: PersonID,
+        infected_intervals: Vec<(Tick, Tick)>,
+        arrived: Tick,
+    ) {
+        if infected_intervals.is_empty()
+            || self.states.get(&person) != Some(&SEIRState::Susceptible)
+        {
+            return;
+        }
+        // Sum the overlap-seconds with each infected contact separately, clipped to the
+        // susceptible person's own stay -- an infected person who already left still contributes
+        // whatever overlap happened before they departed, instead of being dropped entirely.
+        let total_overlap_seconds: f64 = infected_intervals
+            .iter()
+            .map(|(infected_arrived, infected_departed)| {
+                let overlap_start = arrived.max(*infected_arrived);
+                let overlap_end = now.min(*infected_departed);
+                (overlap_end.as_seconds() - overlap_start.as_seconds()).max(0.0)
+            })
+            .sum();
+        let prob = 1.0 - (-self.beta * total_overlap_seconds).exp();
+        if rng.gen_bool(prob.min(1.0).max(0.0)) {
+            self.states.insert(person, SEIRState::Exposed);
+            self.exposed_at.insert(person, now);
+        }
+    }
+
+    // Call every tick (or at least often enough relative to incubation_period) to advance
+    // Exposed->Infected and Infected->Recovered.
+    pub fn step(&mut self, now: Tick) {
+        let mut newly_infected = Vec::new();
+        for (person, exposed_at) in &self.exposed_at {
+            if now.as_seconds() - exposed_at.as_seconds() >= self.incubation_period.as_seconds() {
+                newly_infected.push(*person);
+            }
+        }
+        for person in newly_infected {
+            self.exposed_at.remove(&person);
+            self.states.insert(person, SEIRState::Infected);
+            self.infected_at.insert(person, now);
+        }
+
+        let mut newly_recovered = Vec::new();
+        for (person, infected_at) in &self.infected_at {
+            if now.as_seconds() - infected_at.as_seconds() >= self.infectious_period.as_seconds() {
+                newly_recovered.push(*person);
+            }
+        }
+        for person in newly_recovered {
+            self.infected_at.remove(&person);
+            self.states.insert(person, SEIRState::Recovered);
+        }
+    }
+
+    pub fn state_counts(&self) -> BTreeMap<SEIRState, usize> {
+        let mut counts = BTreeMap::new();
+        for state in vec![
+            SEIRState::Susceptible,
+            SEIRState::Exposed,
+            SEIRState::Infected,
+            SEIRState::Recovered,
+        ] {
+            counts.insert(state, 0);
+        }
+        for state in self.states.values() {
+            *counts.get_mut(state).unwrap() += 1;
+        }
+        counts
+    }
+
+    // The score for GameplayMode::MinimizeInfections: lower is better.
+    pub fn total_ever_infected(&self) -> usize {
+        let counts = self.state_counts();
+        counts[&SEIRState::Infected] + counts[&SEIRState::Recovered]
+    }
+}
+
+fn bldg_infected_intervals(model: &PandemicModel, bldg: BuildingID, now: Tick) -> Vec<(Tick, Tick)> {
+    model
+        .buildings
+        .get(&bldg)
+        .map(|space| space.infected_intervals(model, now))
+        .unwrap_or_else(Vec::new)
+}
+
+fn vehicle_infected_intervals(
+    model: &PandemicModel,
+    vehicle: CarID,
+    now: Tick,
+) -> Vec<(Tick, Tick)> {
+    model
+        .vehicles
+        .get(&vehicle)
+        .map(|space| space.infected_intervals(model, now))
+        .unwrap_or_else(Vec::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rng() -> XorShiftRng {
+        use rand::SeedableRng;
+        XorShiftRng::from_seed([42; 16])
+    }
+
+    fn model_with(beta: f64) -> PandemicModel {
+        let people: Vec<PersonID> = (0..2).map(PersonID).collect();
+        PandemicModel::new(
+            &mut rng(),
+            &people,
+            0,
+            beta,
+            Tick::from_seconds(60 * 60),
+            Tick::from_seconds(60 * 60 * 24 * 7),
+        )
+    }
+
+    #[test]
+    fn no_transmission_without_an_infected_person() {
+        let mut model = model_with(1.0);
+        let bldg = BuildingID(0);
+        let susceptible = PersonID(0);
+        model.person_enters_building(Tick::zero(), susceptible, bldg);
+        model.person_leaves_building(Tick::from_seconds(3600), &mut rng(), susceptible, bldg);
+        assert_eq!(model.state_counts()[&SEIRState::Susceptible], 2);
+    }
+
+    #[test]
+    fn overlap_only_counts_from_when_the_infected_person_arrives() {
+        let mut model = model_with(1.0);
+        model.states.insert(PersonID(1), SEIRState::Infected);
+        model.infected_at.insert(PersonID(1), Tick::zero());
+
+        let bldg = BuildingID(0);
+        let susceptible = PersonID(0);
+        // Susceptible person arrives first and leaves just as the infected person shows up --
+        // there's no overlap, so transmission should never fire.
+        model.person_enters_building(Tick::zero(), susceptible, bldg);
+        model.person_enters_building(Tick::from_seconds(3600), PersonID(1), bldg);
+        model.person_leaves_building(Tick::from_seconds(3600), &mut rng(), susceptible, bldg);
+        assert_eq!(
+            model.states.get(&susceptible),
+            Some(&SEIRState::Susceptible)
+        );
+    }
+
+    #[test]
+    fn overlap_is_still_credited_after_the_infected_person_already_left() {
+        let mut model = model_with(10.0);
+        model.states.insert(PersonID(1), SEIRState::Infected);
+        model.infected_at.insert(PersonID(1), Tick::zero());
+
+        let vehicle = CarID(0);
+        let susceptible = PersonID(0);
+        model.person_boards_vehicle(Tick::zero(), susceptible, vehicle);
+        model.person_boards_vehicle(Tick::zero(), PersonID(1), vehicle);
+        // The infected rider exits one stop early; the hour they shared the vehicle with the
+        // susceptible rider should still count even though they're gone by the time the
+        // susceptible rider alights.
+        model.person_alights_vehicle(Tick::from_seconds(3600), &mut rng(), PersonID(1), vehicle);
+        model.person_alights_vehicle(Tick::from_seconds(7200), &mut rng(), susceptible, vehicle);
+        assert_eq!(model.states.get(&susceptible), Some(&SEIRState::Exposed));
+    }
+
+    #[test]
+    fn long_overlap_with_high_beta_transmits() {
+        let mut model = model_with(10.0);
+        model.states.insert(PersonID(1), SEIRState::Infected);
+        model.infected_at.insert(PersonID(1), Tick::zero());
+
+        let bldg = BuildingID(0);
+        let susceptible = PersonID(0);
+        model.person_enters_building(Tick::zero(), susceptible, bldg);
+        model.person_enters_building(Tick::zero(), PersonID(1), bldg);
+        model.person_leaves_building(Tick::from_seconds(3600), &mut rng(), susceptible, bldg);
+        assert_eq!(model.states.get(&susceptible), Some(&SEIRState::Exposed));
+    }
+
+    #[test]
+    fn step_advances_exposed_to_infected_after_incubation() {
+        let mut model = model_with(0.0);
+        model.states.insert(PersonID(0), SEIRState::Exposed);
+        model.exposed_at.insert(PersonID(0), Tick::zero());
+        model.step(Tick::from_seconds(3600));
+        assert_eq!(model.states.get(&PersonID(0)), Some(&SEIRState::Infected));
+    }
+}
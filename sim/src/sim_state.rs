@@ -0,0 +1,155 @@
+// Copyright 2018 Google LLC, licensed under http://www.apache.org/licenses/LICENSE-2.0
+
+// The slice of `Sim` that owns the optional pandemic/energy submodels and feeds them real
+// building, transit, and driving events. The rest of Sim (routing, the driving/walking state
+// machines, trip scheduling, analytics) lives alongside this file and isn't reproduced here.
+
+use crate::energy::EnergyModel;
+use crate::pandemic::PandemicModel;
+use crate::{CarID, PersonID, Tick};
+use geom::{Distance, Duration, DurationHistogram, DurationStats};
+use map_model::BuildingID;
+use rand_xorshift::XorShiftRng;
+use std::collections::BTreeMap;
+
+pub struct Sim {
+    pub time: Tick,
+    rng: XorShiftRng,
+
+    // None for maps/scenarios that didn't seed a pandemic.
+    pandemic: Option<PandemicModel>,
+    // None for maps that don't have any fuel/charging stations.
+    energy: Option<EnergyModel>,
+    // How many trips this scenario scheduled in total, including ones that haven't started yet.
+    // Distinct from the sim's own finished-trip count, which only grows once trips complete.
+    scheduled_trip_count: usize,
+    // Keyed by bus route name.
+    bus_wait_times: BTreeMap<String, DurationHistogram>,
+}
+
+impl Sim {
+    pub fn new(time: Tick, rng: XorShiftRng, scheduled_trip_count: usize) -> Sim {
+        Sim {
+            time,
+            rng,
+            pandemic: None,
+            energy: None,
+            scheduled_trip_count,
+            bus_wait_times: BTreeMap::new(),
+        }
+    }
+
+    pub fn seed_pandemic_model(&mut self, model: PandemicModel) {
+        self.pandemic = Some(model);
+    }
+
+    pub fn seed_energy_model(&mut self, model: EnergyModel) {
+        self.energy = Some(model);
+    }
+
+    pub fn get_pandemic_model(&self) -> Option<&PandemicModel> {
+        self.pandemic.as_ref()
+    }
+
+    pub fn get_energy_model(&self) -> Option<&EnergyModel> {
+        self.energy.as_ref()
+    }
+
+    pub fn get_scheduled_trip_count(&self) -> usize {
+        self.scheduled_trip_count
+    }
+
+    // Distribution of how long riders have waited for this route's buses so far, for OptimizeBus
+    // challenges. Empty (not missing) for a route nobody's waited for yet.
+    pub fn get_bus_wait_time_stats(&self, route: &str) -> DurationStats {
+        self.bus_wait_times
+            .get(route)
+            .cloned()
+            .unwrap_or_default()
+            .to_stats()
+    }
+
+    // Called once per rider when their bus arrives at their stop.
+    pub fn on_bus_arrival(&mut self, route: &str, wait: Duration) {
+        self.bus_wait_times
+            .entry(route.to_string())
+            .or_insert_with(DurationHistogram::default)
+            .add(wait);
+    }
+
+    // Called wherever a person's walking/driving trip delivers them into a building.
+    pub fn on_bldg_enter(&mut self, person: PersonID, bldg: BuildingID) {
+        let time = self.time;
+        if let Some(ref mut pandemic) = self.pandemic {
+            pandemic.person_enters_building(time, person, bldg);
+        }
+    }
+
+    // Called wherever a person's trip takes them out of a building.
+    pub fn on_bldg_leave(&mut self, person: PersonID, bldg: BuildingID) {
+        let time = self.time;
+        if let Some(ref mut pandemic) = self.pandemic {
+            pandemic.person_leaves_building(time, &mut self.rng, person, bldg);
+        }
+    }
+
+    // Called when a person boards a transit vehicle.
+    pub fn on_vehicle_board(&mut self, person: PersonID, vehicle: CarID) {
+        let time = self.time;
+        if let Some(ref mut pandemic) = self.pandemic {
+            pandemic.person_boards_vehicle(time, person, vehicle);
+        }
+    }
+
+    // Called when a person alights from a transit vehicle.
+    pub fn on_vehicle_alight(&mut self, person: PersonID, vehicle: CarID) {
+        let time = self.time;
+        if let Some(ref mut pandemic) = self.pandemic {
+            pandemic.person_alights_vehicle(time, &mut self.rng, person, vehicle);
+        }
+    }
+
+    // Advances per-tick bookkeeping for the optional submodels. The rest of Sim's per-tick step
+    // (movement, routing, trip completion) lives alongside this, not reproduced here.
+    pub fn step_submodels(&mut self, now: Tick) {
+        self.time = now;
+        if let Some(ref mut pandemic) = self.pandemic {
+            pandemic.step(now);
+        }
+    }
+
+    // Called when a vehicle's trip is spawned, before it starts moving, so doomed trips (longer
+    // than the remaining range, with no station of the vehicle's type on the map) get flagged up
+    // front instead of stranding mid-route.
+    pub fn on_trip_start(&mut self, car: CarID, planned_dist_meters: f64) -> bool {
+        match self.energy {
+            Some(ref mut energy) => energy.check_trip_feasible(car, planned_dist_meters),
+            None => true,
+        }
+    }
+
+    // Called as a vehicle advances along a road segment. Returns true if the router should
+    // detour to the nearest matching station before continuing.
+    pub fn on_vehicle_step(
+        &mut self,
+        car: CarID,
+        dist: Distance,
+        grade: f64,
+        remaining_trip_dist_meters: f64,
+    ) -> bool {
+        match self.energy {
+            Some(ref mut energy) => {
+                energy.step_along_path(car, dist, grade);
+                energy.needs_refuel(car, remaining_trip_dist_meters)
+            }
+            None => false,
+        }
+    }
+
+    // Called when a vehicle finishes dwelling at a fuel/charging station.
+    pub fn on_vehicle_refuel(&mut self, car: CarID) {
+        if let Some(ref mut energy) = self.energy {
+            energy.refuel(car);
+        }
+    }
+}